@@ -16,6 +16,8 @@ pub enum Operation {
     Import(Import),
     Export(Export),
     List,
+    /// Print the log of past imports and exports
+    Audit,
 }
 
 #[derive(Debug, Clone, Args)]
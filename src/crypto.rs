@@ -1,6 +1,7 @@
 use ring::{
     aead::{Aad, LessSafeKey, Nonce, NonceSequence, UnboundKey, AES_256_GCM},
     agreement::{agree_ephemeral, EphemeralPrivateKey, UnparsedPublicKey, X25519},
+    digest::{digest, SHA256},
     hkdf::{Salt, HKDF_SHA256},
     rand::SecureRandom,
 };
@@ -133,6 +134,16 @@ impl LocalKeyPair {
     }
 }
 
+/// Hex encoded SHA-256 of the open box's public key, safe to log.
+pub fn fingerprint(open_box: &OpenBox) -> String {
+    sha256_hex(&open_box.public_key)
+}
+
+/// Hex encoded SHA-256 of `data`
+pub fn sha256_hex(data: &[u8]) -> String {
+    data_encoding::HEXLOWER.encode(digest(&SHA256, data).as_ref())
+}
+
 fn hkdf(shared_secret: &[u8], salt: Salt) -> Result<LessSafeKey, Error> {
     let prk = salt.extract(shared_secret);
     let okm = prk
@@ -2,10 +2,17 @@ use std::path::PathBuf;
 
 use rusqlite::Connection;
 
-use crate::{crypto::LocalKeyPair, load_file, model::fetch_passkeys, schema::Vault, write_file};
+use crate::{
+    crypto::{fingerprint, LocalKeyPair},
+    load_file,
+    model::{append_audit, fetch_passkeys, AuditEntry, AuditOperation},
+    schema::Vault,
+    write_file,
+};
 
-pub fn export(conn: &Connection, path: PathBuf) -> Result<(), clap::Error> {
+pub fn export(conn: &mut Connection, path: PathBuf) -> Result<(), clap::Error> {
     let open_box = load_file(&path)?;
+    let recipient = fingerprint(&open_box);
     let vault = Vault {
         passkeys: fetch_passkeys(conn).map_err(|_| {
             clap::Error::raw(
@@ -19,7 +26,99 @@ pub fn export(conn: &Connection, path: PathBuf) -> Result<(), clap::Error> {
 
     let keys = LocalKeyPair::new(&rng)?;
 
+    let credential_count = vault.passkeys.len();
     let sealed = keys.seal(open_box, vault, &rng)?;
 
-    write_file(path, &sealed)
+    let audit_error = |_| {
+        clap::Error::raw(
+            clap::error::ErrorKind::Io,
+            "Could not record the export in the audit log",
+        )
+    };
+    // The audit entry is only committed once the sealed box is written, so that
+    // every export is recorded and no failed one is
+    let entry = AuditEntry::now(AuditOperation::Export, credential_count, recipient);
+    let tx = append_audit(conn, &entry).map_err(audit_error)?;
+    write_file(path, &sealed)?;
+    tx.commit().map_err(audit_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{create_dir_all, remove_dir_all},
+        path::PathBuf,
+    };
+
+    use rusqlite::Connection;
+
+    use crate::{
+        crypto::{fingerprint, mock_vault, LocalKeyPair},
+        model::{create_db, fetch_audit_log, store_passkeys, AuditEntry, AuditOperation},
+        schema::{OpenBox, SealedBox, ToFileExtension},
+        write_file,
+    };
+
+    use super::export;
+
+    /// An export directory holding a fresh open box, and a database of passkeys to export
+    fn setup(name: &str) -> (PathBuf, OpenBox, Connection) {
+        let dir = std::env::temp_dir().join(format!("uvm-rs-{name}-{}", std::process::id()));
+        create_dir_all(&dir).expect("could not create export directory");
+
+        let rng = ring::rand::SystemRandom::new();
+        let open_box = LocalKeyPair::new(&rng).unwrap().to_open_box().unwrap();
+        write_file(dir.clone(), &open_box).expect("could not write open box");
+
+        let mut conn =
+            create_db("file::memory:".as_ref()).expect("could not create in memory database");
+        let passkeys = mock_vault().passkeys;
+        let import = AuditEntry::now(AuditOperation::Import, passkeys.len(), "importer".into());
+        store_passkeys(&mut conn, &passkeys, &import).expect("could not store passkeys");
+
+        (dir, open_box, conn)
+    }
+
+    #[test]
+    fn export_appends_one_audit_entry() {
+        let (dir, open_box, mut conn) = setup("export");
+        let before = fetch_audit_log(&conn)
+            .expect("could not load audit log")
+            .entries;
+
+        let res = export(&mut conn, dir.clone());
+        remove_dir_all(&dir).expect("could not clean up export directory");
+        res.expect("export failed");
+
+        let log = fetch_audit_log(&conn).expect("could not load audit log");
+        assert!(log.intact);
+        let after = log.entries;
+        assert_eq!(after.len(), before.len() + 1);
+        assert_eq!(after[..before.len()], before[..]);
+        let exported = after.last().unwrap();
+        assert_eq!(exported.operation, AuditOperation::Export);
+        assert_eq!(
+            exported.credential_count,
+            mock_vault().passkeys.len() as u64
+        );
+        assert_eq!(exported.recipient_fingerprint, fingerprint(&open_box));
+    }
+
+    #[test]
+    fn failed_export_is_not_audited() {
+        let (dir, _, mut conn) = setup("failed-export");
+        // a directory where the sealed box should go makes creating it fail
+        create_dir_all(dir.join(format!("uvm-rs.{}", SealedBox::FILE_EXT)))
+            .expect("could not block the sealed box path");
+        let before = fetch_audit_log(&conn).expect("could not load audit log");
+
+        let res = export(&mut conn, dir.clone());
+        remove_dir_all(&dir).expect("could not clean up export directory");
+        assert!(res.is_err(), "export to an unwritable path should fail");
+
+        let after = fetch_audit_log(&conn).expect("could not load audit log");
+        assert!(after.intact);
+        assert_eq!(after.entries, before.entries);
+        assert_eq!(after.head, before.head);
+    }
 }
@@ -8,9 +8,9 @@ use notify::{Event, Watcher};
 use rusqlite::Connection;
 
 use crate::{
-    crypto::LocalKeyPair,
+    crypto::{fingerprint, LocalKeyPair},
     list, load_file,
-    model::store_passkeys,
+    model::{store_passkeys, AuditEntry, AuditOperation},
     schema::{SealedBox, ToFileExtension},
     write_file,
 };
@@ -20,6 +20,7 @@ pub fn import(conn: &mut Connection, path: PathBuf) -> Result<(), clap::Error> {
     let key_pair = LocalKeyPair::new(&rng)?;
 
     let open_box = key_pair.to_open_box()?;
+    let recipient = fingerprint(&open_box);
 
     let dir = if path.extension().is_some() {
         path.parent().unwrap_or(Path::new("."))
@@ -66,7 +67,8 @@ pub fn import(conn: &mut Connection, path: PathBuf) -> Result<(), clap::Error> {
     let sealed = load_file(&sealed_path)?;
     let vault = key_pair.open(sealed)?;
 
-    store_passkeys(conn, &vault.passkeys).map_err(|_| {
+    let entry = AuditEntry::now(AuditOperation::Import, vault.passkeys.len(), recipient);
+    store_passkeys(conn, &vault.passkeys, &entry).map_err(|_| {
         clap::Error::raw(
             clap::error::ErrorKind::Io,
             "Could not store imported passkeys",
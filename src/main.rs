@@ -4,7 +4,8 @@ use std::{
 };
 
 use clap::Parser;
-use model::fetch_passkeys;
+use model::{fetch_audit_log, fetch_passkeys};
+use rusqlite::Connection;
 use schema::{Passkey, ToFileExtension};
 use serde::{Deserialize, Serialize};
 use tabled::{settings::Style, Table};
@@ -26,12 +27,13 @@ fn main() {
     let mut conn = model::create_db(&db_path).unwrap();
     let res = match args.operation {
         cli::Operation::Import(i) => import::import(&mut conn, i.path),
-        cli::Operation::Export(e) => export::export(&conn, e.path),
+        cli::Operation::Export(e) => export::export(&mut conn, e.path),
         cli::Operation::List => {
             let pks = fetch_passkeys(&conn).unwrap();
             list(&pks);
             Ok(())
         }
+        cli::Operation::Audit => audit(&conn),
     };
     if let Err(e) = res {
         e.exit()
@@ -84,3 +86,24 @@ where
 fn list(passkeys: &[Passkey]) {
     println!("{}", Table::new(passkeys).with(Style::markdown()))
 }
+
+fn audit(conn: &Connection) -> Result<(), clap::Error> {
+    let log = fetch_audit_log(conn).map_err(|_| {
+        clap::Error::raw(
+            clap::error::ErrorKind::Io,
+            "Could not fetch the audit log from database",
+        )
+    })?;
+    println!("{}", Table::new(log.entries).with(Style::markdown()));
+    if let Some(head) = log.head {
+        println!("Head: {head}");
+    }
+    if log.intact {
+        Ok(())
+    } else {
+        Err(clap::Error::raw(
+            clap::error::ErrorKind::Io,
+            "The audit log has been tampered with",
+        ))
+    }
+}
@@ -1,8 +1,18 @@
-use std::path::Path;
+use std::{
+    fmt::Display,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use rusqlite::{types::FromSqlError, Connection, Result};
+use rusqlite::{
+    types::FromSqlError, Connection, OptionalExtension, Result, Transaction, TransactionBehavior,
+};
+use tabled::Tabled;
 
-use crate::schema::{base64, try_from_base64, Passkey};
+use crate::{
+    crypto::sha256_hex,
+    schema::{base64, try_from_base64, Passkey},
+};
 
 /// The database is NOT encrypted because this is for demonstration purposes
 pub fn create_db(path: &Path) -> Result<Connection> {
@@ -42,8 +52,14 @@ pub fn fetch_passkeys(conn: &Connection) -> Result<Vec<Passkey>> {
     res.collect()
 }
 
-pub fn store_passkeys(conn: &mut Connection, passkeys: &[Passkey]) -> Result<()> {
-    let tx = conn.transaction()?;
+/// Store imported passkeys together with the audit entry recording their import,
+/// so that neither is persisted without the other.
+pub fn store_passkeys(
+    conn: &mut Connection,
+    passkeys: &[Passkey],
+    entry: &AuditEntry,
+) -> Result<()> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
     {
         let mut stmt = tx.prepare(
             r#"INSERT OR REPLACE INTO passkeys(
@@ -73,16 +89,221 @@ pub fn store_passkeys(conn: &mut Connection, passkeys: &[Passkey]) -> Result<()>
             ))?;
         }
     }
+    insert_audit(&tx, entry)?;
     tx.commit()?;
 
     Ok(())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOperation {
+    Import,
+    Export,
+}
+
+impl AuditOperation {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditOperation::Import => "import",
+            AuditOperation::Export => "export",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        [AuditOperation::Import, AuditOperation::Export]
+            .into_iter()
+            .find(|operation| operation.as_str() == name)
+    }
+}
+
+impl Display for AuditOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A record of a completed import or export. Must never hold key material or vault contents.
+#[derive(Debug, Clone, PartialEq, Eq, Tabled)]
+pub struct AuditEntry {
+    /// Seconds since the unix epoch
+    #[tabled(rename = "Timestamp", display_with = "rfc3339")]
+    pub timestamp: u64,
+
+    #[tabled(rename = "Operation")]
+    pub operation: AuditOperation,
+
+    #[tabled(rename = "Credentials")]
+    pub credential_count: u64,
+
+    /// Fingerprint of the public key the vault was sealed for
+    #[tabled(rename = "Recipient")]
+    pub recipient_fingerprint: String,
+}
+
+impl AuditEntry {
+    pub fn now(
+        operation: AuditOperation,
+        credential_count: usize,
+        recipient_fingerprint: String,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        AuditEntry {
+            timestamp,
+            operation,
+            credential_count: credential_count as u64,
+            recipient_fingerprint,
+        }
+    }
+
+    /// Chain this entry onto the hash of the previous one, so that editing any row,
+    /// or deleting any row but the newest, breaks the chain. Truncating the newest
+    /// rows leaves a valid chain and is only caught by comparing [`AuditLog::head`]
+    /// against a copy kept elsewhere.
+    fn chained_hash(&self, previous: &str) -> String {
+        let record = format!(
+            "{previous}|{}|{}|{}|{}",
+            self.timestamp, self.operation, self.credential_count, self.recipient_fingerprint
+        );
+        sha256_hex(record.as_bytes())
+    }
+}
+
+/// Format seconds since the unix epoch as an RFC 3339 UTC timestamp
+fn rfc3339(timestamp: &u64) -> String {
+    let (days, secs) = (timestamp / 86400, timestamp % 86400);
+    // Civil date from days since the epoch, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Append `entry` inside a transaction that the caller commits once the audited
+/// operation has succeeded. Dropping it instead rolls the entry back.
+pub fn append_audit<'a>(conn: &'a mut Connection, entry: &AuditEntry) -> Result<Transaction<'a>> {
+    // Immediate so that concurrent writers cannot chain onto the same previous hash
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    insert_audit(&tx, entry)?;
+    Ok(tx)
+}
+
+fn insert_audit(tx: &Transaction, entry: &AuditEntry) -> Result<()> {
+    let previous: String = tx
+        .query_row(
+            r#"SELECT "hash" FROM "audit_log" ORDER BY "id" DESC LIMIT 1"#,
+            [],
+            |row| row.get("hash"),
+        )
+        .optional()?
+        .unwrap_or_default();
+
+    tx.execute(
+        r#"INSERT INTO audit_log(
+            "timestamp",
+            "operation",
+            "count",
+            "fingerprint",
+            "hash"
+        )
+    VALUES (?1, ?2, ?3, ?4, ?5)
+        "#,
+        (
+            entry.timestamp,
+            entry.operation.as_str(),
+            entry.credential_count,
+            &entry.recipient_fingerprint,
+            entry.chained_hash(&previous),
+        ),
+    )?;
+
+    Ok(())
+}
+
+pub struct AuditLog {
+    /// Entries in insertion order, without any row that could not be parsed
+    pub entries: Vec<AuditEntry>,
+    /// False if any row failed to parse or does not match its chained hash
+    pub intact: bool,
+    /// Hash of the newest row, to be recorded outside the database
+    pub head: Option<String>,
+}
+
+pub fn fetch_audit_log(conn: &Connection) -> Result<AuditLog> {
+    let mut stmt = conn.prepare(
+        r#"SELECT
+            "timestamp",
+            "operation",
+            "count",
+            "fingerprint",
+            "hash"
+        from "audit_log" ORDER BY "id""#,
+    )?;
+
+    // Values that don't parse can only come from tampering, so they break the
+    // chain rather than failing the whole query
+    let rows = stmt.query_map([], |row| {
+        let operation = row
+            .get_ref("operation")?
+            .as_str()
+            .ok()
+            .and_then(AuditOperation::parse);
+        let entry = (|| {
+            Some(AuditEntry {
+                timestamp: row.get("timestamp").ok()?,
+                operation: operation?,
+                credential_count: row.get("count").ok()?,
+                recipient_fingerprint: row.get("fingerprint").ok()?,
+            })
+        })();
+        Ok((entry, row.get::<_, String>("hash").ok()))
+    })?;
+
+    let mut entries = Vec::new();
+    let mut previous = String::new();
+    let mut head = None;
+    let mut intact = true;
+    for row in rows {
+        let (entry, hash) = row?;
+        let hash = hash.unwrap_or_default();
+        match entry {
+            Some(entry) => {
+                intact &= entry.chained_hash(&previous) == hash;
+                entries.push(entry);
+            }
+            None => intact = false,
+        }
+        head = Some(hash.clone());
+        previous = hash;
+    }
+    Ok(AuditLog {
+        entries,
+        intact,
+        head,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use crate::crypto::mock_vault;
 
-    use super::{create_db, fetch_passkeys, store_passkeys};
+    use super::{
+        append_audit, create_db, fetch_audit_log, fetch_passkeys, rfc3339, store_passkeys,
+        AuditEntry, AuditOperation,
+    };
 
     #[test]
     fn database_round_trip() {
@@ -90,7 +311,8 @@ mod tests {
         passkeys.sort_by(|a, b| a.credential_id.cmp(&b.credential_id));
         let mut conn =
             create_db("file::memory:".as_ref()).expect("could not create in memory database");
-        store_passkeys(&mut conn, &passkeys).expect("could not store passkeys");
+        let entry = AuditEntry::now(AuditOperation::Import, passkeys.len(), "fingerprint".into());
+        store_passkeys(&mut conn, &passkeys, &entry).expect("could not store passkeys");
         let mut retrieved = fetch_passkeys(&conn).expect("could not load stored passkeys");
         retrieved.sort_by(|a, b| a.credential_id.cmp(&b.credential_id));
 
@@ -102,4 +324,82 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn audit_log_detects_tampering() {
+        let mut conn =
+            create_db("file::memory:".as_ref()).expect("could not create in memory database");
+        for operation in [AuditOperation::Import, AuditOperation::Export] {
+            let entry = AuditEntry::now(operation, 2, "fingerprint".into());
+            append_audit(&mut conn, &entry)
+                .and_then(|tx| tx.commit())
+                .expect("could not append audit entry");
+        }
+        let log = fetch_audit_log(&conn).expect("could not load audit log");
+        assert_eq!(log.entries.len(), 2);
+        assert!(log.intact);
+
+        conn.execute(r#"UPDATE "audit_log" SET "count" = 1 WHERE "id" = 1"#, [])
+            .expect("could not tamper with audit log");
+        let log = fetch_audit_log(&conn).expect("could not load audit log");
+        assert!(!log.intact);
+    }
+
+    #[test]
+    fn audit_log_detects_deleted_rows() {
+        let mut conn =
+            create_db("file::memory:".as_ref()).expect("could not create in memory database");
+        for count in 1..=3 {
+            let entry = AuditEntry::now(AuditOperation::Export, count, "fingerprint".into());
+            append_audit(&mut conn, &entry)
+                .and_then(|tx| tx.commit())
+                .expect("could not append audit entry");
+        }
+        let head = fetch_audit_log(&conn)
+            .expect("could not load audit log")
+            .head
+            .expect("log should not be empty");
+
+        conn.execute(r#"DELETE FROM "audit_log" WHERE "id" = 2"#, [])
+            .expect("could not tamper with audit log");
+        let log = fetch_audit_log(&conn).expect("could not load audit log");
+        assert!(!log.intact);
+        assert_eq!(log.entries.len(), 2);
+
+        // truncation keeps the chain valid, only the head reveals it
+        conn.execute(r#"DELETE FROM "audit_log" WHERE "id" >= 2"#, [])
+            .expect("could not tamper with audit log");
+        let log = fetch_audit_log(&conn).expect("could not load audit log");
+        assert!(log.intact);
+        assert_ne!(log.head, Some(head));
+    }
+
+    #[test]
+    fn audit_log_reports_unparseable_rows_as_tampered() {
+        let mut conn =
+            create_db("file::memory:".as_ref()).expect("could not create in memory database");
+        for operation in [AuditOperation::Import, AuditOperation::Export] {
+            let entry = AuditEntry::now(operation, 2, "fingerprint".into());
+            append_audit(&mut conn, &entry)
+                .and_then(|tx| tx.commit())
+                .expect("could not append audit entry");
+        }
+
+        conn.execute(
+            r#"UPDATE "audit_log" SET "operation" = 'delete' WHERE "id" = 1"#,
+            [],
+        )
+        .expect("could not tamper with audit log");
+        let log = fetch_audit_log(&conn).expect("tampered rows should not fail the query");
+        assert!(!log.intact);
+        assert_eq!(log.entries.len(), 1);
+        assert_eq!(log.entries[0].operation, AuditOperation::Export);
+    }
+
+    #[test]
+    fn timestamps_display_as_rfc3339() {
+        assert_eq!(rfc3339(&0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(&951782400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(&1700000000), "2023-11-14T22:13:20Z");
+    }
 }
@@ -7,4 +7,13 @@ CREATE TABLE IF NOT EXISTS passkeys (
     "counter"   INTEGER DEFAULT 0 NOT NULL,
     "key_alg"   TEXT NOT NULL,
     "key"       TEXT NOT NULL
-);
\ No newline at end of file
+);
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    "id"            INTEGER PRIMARY KEY AUTOINCREMENT,
+    "timestamp"     INTEGER NOT NULL,
+    "operation"     TEXT NOT NULL,
+    "count"         INTEGER NOT NULL,
+    "fingerprint"   TEXT NOT NULL,
+    "hash"          TEXT NOT NULL
+);
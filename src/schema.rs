@@ -1,4 +1,4 @@
-use data_encoding::{Specification, BASE64, BASE64URL, BASE64_NOPAD};
+use data_encoding::{Encoding, BASE64, BASE64URL, BASE64URL_NOPAD, BASE64_NOPAD};
 use serde::{Deserialize, Serialize};
 use tabled::Tabled;
 
@@ -120,22 +120,110 @@ pub fn base64(data: &[u8]) -> String {
 }
 
 /// Try parsing from base64 with or without padding
+///
+/// Non-canonical inputs (nonzero trailing bits, malformed padding) are rejected, so
+/// each byte sequence has exactly one accepted padded form and one unpadded form,
+/// and `base64(decode(x))` is the canonical form of `x`.
 pub(crate) fn try_from_base64(input: &str) -> Option<Vec<u8>> {
-    let padding = BASE64.specification().padding.unwrap();
-    let sane_string = input.trim_end_matches(padding);
-    BASE64_NOPAD.decode(sane_string.as_bytes()).ok()
+    decode_optionally_padded(&BASE64, &BASE64_NOPAD, input)
 }
 
 /// Try parsing from base64url with or without padding
+///
+/// Same canonicalization rules as [`try_from_base64`].
 pub fn try_from_base64url(input: &str) -> Option<Vec<u8>> {
-    let specs = BASE64URL.specification();
-    let padding = specs.padding.unwrap();
-    let specs = Specification {
-        check_trailing_bits: false,
-        padding: None,
-        ..specs
+    decode_optionally_padded(&BASE64URL, &BASE64URL_NOPAD, input)
+}
+
+/// Use the padded encoding only when the input carries padding, and accept the result
+/// only if it re-encodes to the input. The padded decoder alone also accepts
+/// concatenated padded chunks such as `AA==AA==`.
+fn decode_optionally_padded(
+    padded: &Encoding,
+    unpadded: &Encoding,
+    input: &str,
+) -> Option<Vec<u8>> {
+    let padding = padded.specification().padding.unwrap();
+    let encoding = if input.ends_with(padding) {
+        padded
+    } else {
+        unpadded
     };
-    let encoding = specs.encoding().unwrap();
-    let sane_string = input.trim_end_matches(padding);
-    encoding.decode(sane_string.as_bytes()).ok()
+    encoding
+        .decode(input.as_bytes())
+        .ok()
+        .filter(|bytes| encoding.encode(bytes) == input)
+}
+
+#[cfg(test)]
+mod tests {
+    use data_encoding::{BASE64URL, BASE64URL_NOPAD, BASE64_NOPAD};
+    use ring::rand::{SecureRandom, SystemRandom};
+
+    use super::{base64, try_from_base64, try_from_base64url};
+
+    /// Random inputs of every length up to a few full blocks, to cover all padding cases.
+    fn random_inputs() -> impl Iterator<Item = Vec<u8>> {
+        let rng = SystemRandom::new();
+        (0..64).flat_map(move |len| {
+            (0..8)
+                .map(|_| {
+                    let mut bytes = vec![0; len];
+                    rng.fill(&mut bytes).unwrap();
+                    bytes
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+
+    #[test]
+    fn base64_round_trip_is_canonical() {
+        for bytes in random_inputs() {
+            let padded = base64(&bytes);
+            let unpadded = BASE64_NOPAD.encode(&bytes);
+            for encoded in [&padded, &unpadded] {
+                let decoded = try_from_base64(encoded).expect("valid base64 should decode");
+                assert_eq!(decoded, bytes);
+                assert_eq!(base64(&decoded), padded);
+            }
+        }
+    }
+
+    #[test]
+    fn base64url_round_trip_is_canonical() {
+        for bytes in random_inputs() {
+            let padded = BASE64URL.encode(&bytes);
+            let unpadded = BASE64URL_NOPAD.encode(&bytes);
+            for encoded in [&padded, &unpadded] {
+                let decoded = try_from_base64url(encoded).expect("valid base64url should decode");
+                assert_eq!(decoded, bytes);
+                assert_eq!(BASE64URL.encode(&decoded), padded);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_nonzero_trailing_bits() {
+        // "AA" and "AAA" are the canonical encodings, the others alias the same bytes
+        for input in ["AB", "AB==", "AAB", "AAB=", "AP", "AAD"] {
+            assert_eq!(try_from_base64(input), None, "{input}");
+            assert_eq!(try_from_base64url(input), None, "{input}");
+        }
+        assert_eq!(try_from_base64url("_-"), None);
+        assert_eq!(try_from_base64url("_w"), Some(vec![0xff]));
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        for input in [
+            "A", "AAAAA", "A===", "AA=", "AA===", "AAA==", "A=AA", "=AAA", "AA AA", "AA*A",
+            "AAAA\n", "AA==AA==", "AAA=AAA=", "AA==AAA=",
+        ] {
+            assert_eq!(try_from_base64(input), None, "{input}");
+            assert_eq!(try_from_base64url(input), None, "{input}");
+        }
+        // each decoder only accepts its own alphabet
+        assert_eq!(try_from_base64("-_-_"), None);
+        assert_eq!(try_from_base64url("+/+/"), None);
+    }
 }